@@ -1,8 +1,12 @@
-use std::path::Path;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::fs;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use serde::{Serialize, Deserialize};
 use regex::Regex;
-use anyhow::{Result, Context};
+use anyhow::{Result, Context as _};
+use sha2::{Digest, Sha256};
 use google_cloud_vision::v1::{image_annotator_client::ImageAnnotatorClient, Feature, FeatureType, Image, AnnotateImageRequest};
 
 mod google_cloud_vision {
@@ -33,6 +37,254 @@ struct AnalysisResult {
     response: String,
 }
 
+/// schema.org `NutritionInformation`, so downstream consumers (recipe apps,
+/// search indexers) can ingest a scan result as standard structured data
+/// instead of our ad-hoc field names.
+#[derive(Debug, Serialize, Deserialize)]
+struct SchemaOrgNutrition {
+    #[serde(rename = "@type")]
+    schema_type: String,
+    #[serde(rename = "servingSize", skip_serializing_if = "Option::is_none")]
+    serving_size: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    calories: Option<String>,
+    #[serde(rename = "fatContent", skip_serializing_if = "Option::is_none")]
+    fat_content: Option<String>,
+    #[serde(rename = "saturatedFatContent", skip_serializing_if = "Option::is_none")]
+    saturated_fat_content: Option<String>,
+    #[serde(rename = "cholesterolContent", skip_serializing_if = "Option::is_none")]
+    cholesterol_content: Option<String>,
+    #[serde(rename = "sodiumContent", skip_serializing_if = "Option::is_none")]
+    sodium_content: Option<String>,
+    #[serde(rename = "carbohydrateContent", skip_serializing_if = "Option::is_none")]
+    carbohydrate_content: Option<String>,
+    #[serde(rename = "fiberContent", skip_serializing_if = "Option::is_none")]
+    fiber_content: Option<String>,
+    #[serde(rename = "sugarContent", skip_serializing_if = "Option::is_none")]
+    sugar_content: Option<String>,
+    #[serde(rename = "proteinContent", skip_serializing_if = "Option::is_none")]
+    protein_content: Option<String>,
+}
+
+/// schema.org `Recipe` wrapper around a `SchemaOrgNutrition`, for consumers
+/// that expect nutrition data nested under a recipe rather than standalone.
+#[derive(Debug, Serialize, Deserialize)]
+struct SchemaOrgRecipe {
+    #[serde(rename = "@context")]
+    context: String,
+    #[serde(rename = "@type")]
+    schema_type: String,
+    nutrition: SchemaOrgNutrition,
+    #[serde(rename = "recipeIngredient", skip_serializing_if = "Option::is_none")]
+    recipe_ingredient: Option<Vec<String>>,
+}
+
+/// Reformats a captured "12g" / "500 mg" style value into schema.org's
+/// "<number> <unit>" convention, leaving anything it can't parse untouched.
+fn schema_org_amount(value: &Option<String>) -> Option<String> {
+    value.as_ref().map(|v| {
+        Regex::new(r"(?i)^(\d+\.?\d*)\s*([a-zA-Z%]+)$")
+            .unwrap()
+            .captures(v.trim())
+            .map(|caps| format!("{} {}", &caps[1], caps[2].to_lowercase()))
+            .unwrap_or_else(|| v.clone())
+    })
+}
+
+impl From<&NutritionData> for SchemaOrgNutrition {
+    fn from(data: &NutritionData) -> Self {
+        SchemaOrgNutrition {
+            schema_type: "NutritionInformation".to_string(),
+            serving_size: data.serving_size.clone(),
+            calories: data.calories.clone(),
+            fat_content: schema_org_amount(&data.total_fat),
+            saturated_fat_content: schema_org_amount(&data.saturated_fat),
+            cholesterol_content: schema_org_amount(&data.cholesterol),
+            sodium_content: schema_org_amount(&data.sodium),
+            carbohydrate_content: schema_org_amount(&data.total_carbohydrate),
+            fiber_content: schema_org_amount(&data.dietary_fiber),
+            sugar_content: schema_org_amount(&data.sugars),
+            protein_content: schema_org_amount(&data.protein),
+        }
+    }
+}
+
+impl From<&NutritionData> for SchemaOrgRecipe {
+    fn from(data: &NutritionData) -> Self {
+        // Uses the parenthesis-aware `parse_ingredients` rather than a naive
+        // `split(',')`, so sub-ingredient lists like "Enriched Flour (Wheat
+        // Flour, Niacin, Reduced Iron)" stay a single recipeIngredient entry.
+        let recipe_ingredient = data
+            .ingredients
+            .as_ref()
+            .map(|ingredients| parse_ingredients(ingredients).iter().map(format_ingredient).collect());
+
+        SchemaOrgRecipe {
+            context: "https://schema.org".to_string(),
+            schema_type: "Recipe".to_string(),
+            nutrition: SchemaOrgNutrition::from(data),
+            recipe_ingredient,
+        }
+    }
+}
+
+/// Unit a parsed ingredient amount is expressed in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+enum Unit {
+    Gram,
+    Milligram,
+    Milliliter,
+    Percent,
+    None,
+}
+
+impl Unit {
+    fn from_str(unit: &str) -> Unit {
+        match unit.to_lowercase().as_str() {
+            "g" | "gram" | "grams" => Unit::Gram,
+            "mg" | "milligram" | "milligrams" => Unit::Milligram,
+            "ml" | "milliliter" | "milliliters" | "millilitre" | "millilitres" => Unit::Milliliter,
+            "%" | "percent" => Unit::Percent,
+            _ => Unit::None,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Unit::Gram => "g",
+            Unit::Milligram => "mg",
+            Unit::Milliliter => "ml",
+            Unit::Percent => "%",
+            Unit::None => "",
+        }
+    }
+}
+
+/// Renders a parsed `Ingredient` back into a display string, e.g.
+/// `Ingredient { name: "Salt", amount: Some(2.0), unit: Unit::Gram }`
+/// becomes "2g Salt".
+fn format_ingredient(ingredient: &Ingredient) -> String {
+    match ingredient.amount {
+        Some(amount) => format!("{}{} {}", amount, ingredient.unit.as_str(), ingredient.name),
+        None => ingredient.name.clone(),
+    }
+}
+
+/// A single parsed ingredient, with its amount normalized to a `Unit` when
+/// the ingredients blob carries a leading or trailing quantity (e.g. "2g
+/// Salt" or "Salt 2g").
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Ingredient {
+    name: String,
+    amount: Option<f64>,
+    unit: Unit,
+}
+
+/// Splits an ingredients blob on top-level commas/semicolons, leaving
+/// parenthesized sub-ingredient lists (e.g. "Enriched Flour (Wheat Flour,
+/// Niacin, Reduced Iron)") intact as part of their parent entry.
+fn split_top_level(text: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut depth = 0i32;
+    let mut current = String::new();
+
+    for c in text.chars() {
+        match c {
+            '(' => {
+                depth += 1;
+                current.push(c);
+            }
+            ')' => {
+                depth -= 1;
+                current.push(c);
+            }
+            ',' | ';' if depth <= 0 => {
+                parts.push(current.trim().to_string());
+                current = String::new();
+            }
+            _ => current.push(c),
+        }
+    }
+    if !current.trim().is_empty() {
+        parts.push(current.trim().to_string());
+    }
+
+    parts.into_iter().filter(|part| !part.is_empty()).collect()
+}
+
+/// Pulls a leading or trailing quantity+unit off a single ingredient entry,
+/// e.g. "2g Salt" or "Salt 2g" both become `("Salt", Some(2.0), Unit::Gram)`.
+fn extract_amount_unit(entry: &str) -> (String, Option<f64>, Unit) {
+    let leading = Regex::new(r"(?i)^\s*(\d+\.?\d*)\s*(g|mg|ml|%|grams?|milligrams?|milliliters?|millilitres?)?\s+(.+)$").unwrap();
+    if let Some(caps) = leading.captures(entry) {
+        let amount = caps.get(1).and_then(|m| m.as_str().parse().ok());
+        let unit = caps.get(2).map(|m| Unit::from_str(m.as_str())).unwrap_or(Unit::None);
+        let name = caps.get(3).unwrap().as_str().trim().to_string();
+        return (name, amount, unit);
+    }
+
+    let trailing = Regex::new(r"(?i)^(.+?)\s+(\d+\.?\d*)\s*(g|mg|ml|%|grams?|milligrams?|milliliters?|millilitres?)?\s*$").unwrap();
+    if let Some(caps) = trailing.captures(entry) {
+        let name = caps.get(1).unwrap().as_str().trim().to_string();
+        let amount = caps.get(2).and_then(|m| m.as_str().parse().ok());
+        let unit = caps.get(3).map(|m| Unit::from_str(m.as_str())).unwrap_or(Unit::None);
+        return (name, amount, unit);
+    }
+
+    (entry.trim().to_string(), None, Unit::None)
+}
+
+/// Parses an ingredients blob (as extracted into `NutritionData::ingredients`)
+/// into structured `Ingredient`s with normalized amounts and units.
+pub fn parse_ingredients(ingredients_text: &str) -> Vec<Ingredient> {
+    split_top_level(ingredients_text)
+        .into_iter()
+        .map(|entry| {
+            let (name, amount, unit) = extract_amount_unit(&entry);
+            Ingredient { name, amount, unit }
+        })
+        .collect()
+}
+
+/// Merges ingredients parsed from several scanned labels, summing amounts
+/// for entries that share a (name, unit) key so users can total nutrition
+/// across multiple products in a meal.
+pub fn merge_ingredients(ingredient_lists: &[Vec<Ingredient>]) -> Vec<Ingredient> {
+    let mut totals: HashMap<(String, Unit), Option<f64>> = HashMap::new();
+
+    for ingredient in ingredient_lists.iter().flatten() {
+        let key = (ingredient.name.clone(), ingredient.unit);
+        let total = totals.entry(key).or_insert(None);
+        *total = match (*total, ingredient.amount) {
+            (Some(existing), Some(amount)) => Some(existing + amount),
+            (None, Some(amount)) => Some(amount),
+            (existing, None) => existing,
+        };
+    }
+
+    let mut merged: Vec<Ingredient> = totals
+        .into_iter()
+        .map(|((name, unit), amount)| Ingredient { name, amount, unit })
+        .collect();
+
+    merged.sort_by(|a, b| (&a.name, a.unit).cmp(&(&b.name, b.unit)));
+    merged
+}
+
+/// Output mode for `analyze_nutrition_label`: our native shape, or
+/// schema.org structured data (standalone or nested in a `Recipe`).
+pub enum OutputFormat {
+    Native,
+    SchemaOrgNutrition,
+    SchemaOrgRecipe,
+}
+
+pub enum AnalysisOutput {
+    Native(AnalysisResult),
+    SchemaOrgNutrition(SchemaOrgNutrition),
+    SchemaOrgRecipe(SchemaOrgRecipe),
+}
+
 pub async fn detect_text(image_path: &str) -> Result<String> {
     let client = ImageAnnotatorClient::new().await?;
     
@@ -65,6 +317,92 @@ pub async fn detect_text(image_path: &str) -> Result<String> {
     Ok(text)
 }
 
+/// Whether a cached value has been fetched yet for a given key.
+enum Fetchable<T> {
+    None,
+    Fetched(T),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheEntry {
+    extracted_text: String,
+    fetched_at: u64,
+}
+
+/// On-disk cache of `detect_text` results keyed by a content hash of the
+/// image bytes, so repeated analysis of the same image (and test suites)
+/// avoid redundant, billable Vision API calls. Entries older than
+/// `local_ttl` are treated as unfetched and re-requested.
+pub struct OcrCache {
+    dir: PathBuf,
+    local_ttl: Duration,
+}
+
+impl OcrCache {
+    pub fn new(dir: impl Into<PathBuf>, local_ttl: Duration) -> Result<OcrCache> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(OcrCache { dir, local_ttl })
+    }
+
+    fn entry_path(&self, hash: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", hash))
+    }
+
+    fn get(&self, hash: &str) -> Fetchable<String> {
+        let entry: CacheEntry = match fs::read_to_string(self.entry_path(hash))
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+        {
+            Some(entry) => entry,
+            None => return Fetchable::None,
+        };
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        if now.saturating_sub(entry.fetched_at) > self.local_ttl.as_secs() {
+            return Fetchable::None;
+        }
+
+        Fetchable::Fetched(entry.extracted_text)
+    }
+
+    fn put(&self, hash: &str, extracted_text: &str) -> Result<()> {
+        let fetched_at = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        let entry = CacheEntry {
+            extracted_text: extracted_text.to_string(),
+            fetched_at,
+        };
+        fs::write(self.entry_path(hash), serde_json::to_string_pretty(&entry)?)?;
+        Ok(())
+    }
+}
+
+/// Content hash used as the cache key. Must be stable across Rust
+/// versions/processes since entries persist as `{hash}.json` files on disk
+/// (unlike `DefaultHasher`, whose docs explicitly disclaim cross-version
+/// stability), so a toolchain upgrade can't silently invalidate the cache.
+fn hash_image_bytes(bytes: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(bytes);
+    format!("{:x}", hasher.finalize())
+}
+
+/// Cache-aware wrapper around `detect_text`: re-fetches from the Vision API
+/// only when there's no cached entry for this image's content hash, or the
+/// cached entry is older than the cache's `local_ttl`.
+async fn detect_text_cached(image_path: &str, cache: &OcrCache) -> Result<String> {
+    let image_content = fs::read(image_path)?;
+    let hash = hash_image_bytes(&image_content);
+
+    if let Fetchable::Fetched(extracted_text) = cache.get(&hash) {
+        return Ok(extracted_text);
+    }
+
+    let extracted_text = detect_text(image_path).await?;
+    cache.put(&hash, &extracted_text)?;
+    Ok(extracted_text)
+}
+
 fn normalize_units(text: &str) -> String {
     let replacements = vec![
         (r"(?i)\b(\d+)\s*m9\b", "$1 mg"),
@@ -82,7 +420,167 @@ fn normalize_units(text: &str) -> String {
     result
 }
 
-fn parse_nutrition_info(text: &str) -> NutritionData {
+/// Language a nutrition label's OCR text is written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Lang {
+    English,
+    French,
+    German,
+    Spanish,
+}
+
+/// Parsing context threaded through `parse_nutrition_info` and
+/// `check_for_allergens` so non-English labels are matched against the
+/// right pattern and vocabulary tables instead of always falling back to
+/// the English ones.
+#[derive(Debug, Clone, Copy)]
+pub struct Context {
+    pub lang: Lang,
+}
+
+impl Context {
+    pub fn new(lang: Lang) -> Context {
+        Context { lang }
+    }
+}
+
+/// Auto-detects a label's language from keyword hits in the OCR text,
+/// falling back to English when no other language scores higher.
+fn detect_language(text: &str) -> Lang {
+    let lower = text.to_lowercase();
+
+    let language_keywords = vec![
+        (Lang::French, vec!["lipides", "glucides", "sel", "ingrédients", "valeurs nutritionnelles"]),
+        (Lang::German, vec!["natrium", "eiweiß", "zutaten", "kohlenhydrate", "nährwerte"]),
+        (Lang::Spanish, vec!["grasas", "azúcares", "ingredientes", "carbohidratos", "información nutricional"]),
+    ];
+
+    let mut best = Lang::English;
+    let mut best_hits = 0;
+    for (lang, keywords) in language_keywords {
+        let hits = keywords.iter().filter(|kw| lower.contains(*kw)).count();
+        if hits > best_hits {
+            best_hits = hits;
+            best = lang;
+        }
+    }
+
+    best
+}
+
+fn serving_patterns(lang: Lang) -> Vec<&'static str> {
+    match lang {
+        Lang::English => vec![
+            r"(?i)Serving\s+Size[:\s]*([^\.]*?)(Serving|Amount|Calories|Per)",
+            r"(?i)Serving\s+Size[:\s]*([0-9]+\s*[a-zA-Z]*)",
+            r"(?i)Serving[:\s]*([0-9]+\s*[a-zA-Z]*)",
+        ],
+        Lang::French => vec![
+            r"(?i)Portion[:\s]*([0-9]+\s*[a-zA-Z]*)",
+            r"(?i)Par\s+portion\s+de[:\s]*([0-9]+\s*[a-zA-Z]*)",
+        ],
+        Lang::German => vec![
+            r"(?i)Portionsgr(?:ö|oe)(?:ß|ss)e[:\s]*([0-9]+\s*[a-zA-Z]*)",
+            r"(?i)Pro\s+Portion[:\s]*([0-9]+\s*[a-zA-Z]*)",
+        ],
+        Lang::Spanish => vec![
+            r"(?i)Tama(?:ñ|n)o\s+de\s+la\s+porci(?:ó|o)n[:\s]*([0-9]+\s*[a-zA-Z]*)",
+            r"(?i)Por\s+porci(?:ó|o)n[:\s]*([0-9]+\s*[a-zA-Z]*)",
+        ],
+    }
+}
+
+fn calories_patterns(lang: Lang) -> Vec<&'static str> {
+    match lang {
+        Lang::English => vec![
+            r"(?i)Calories\s+(\d+)",
+            r"(?i)Energy\s+(\d+)\s*kcal",
+            r"(?i)Cal[:\s]*(\d+)",
+        ],
+        Lang::French => vec![r"(?i)(?:Calories|Énergie|Energie)[:\s]*(\d+)\s*(?:kcal)?"],
+        Lang::German => vec![r"(?i)(?:Kalorien|Energie|Brennwert)[:\s]*(\d+)\s*(?:kcal)?"],
+        Lang::Spanish => vec![r"(?i)(?:Calorías|Energía)[:\s]*(\d+)\s*(?:kcal)?"],
+    }
+}
+
+fn nutrient_patterns(lang: Lang) -> Vec<(&'static str, &'static str)> {
+    match lang {
+        Lang::English => vec![
+            ("total_fat", r"(?i)Total\s+Fat\s*[:\s]*\s*(\d+\.?\d*\s*[g%])"),
+            ("saturated_fat", r"(?i)Saturated\s+Fat\s*[:\s]*\s*(\d+\.?\d*\s*[g%])"),
+            ("cholesterol", r"(?i)Cholesterol\s*[:\s]*\s*(\d+\s*mg)"),
+            ("sodium", r"(?i)Sodium\s*[:\s]*\s*(\d+\s*mg)"),
+            ("total_carbohydrate", r"(?i)(Total\s+)?Carbohydrate\s*[:\s]*\s*(\d+\.?\d*\s*[g%])"),
+            ("dietary_fiber", r"(?i)(Dietary\s+)?Fiber\s*[:\s]*\s*(\d+\.?\d*\s*[g%])"),
+            ("sugars", r"(?i)Sugars\s*[:\s]*\s*(\d+\.?\d*\s*[g%])"),
+            ("protein", r"(?i)Protein\s*[:\s]*\s*(\d+\.?\d*\s*[g%])"),
+        ],
+        Lang::French => vec![
+            ("total_fat", r"(?i)Lipides\s*[:\s]*\s*(\d+\.?\d*\s*[g%])"),
+            ("saturated_fat", r"(?i)(?:dont\s+)?Acides\s+gras\s+satur(?:é|e)s\s*[:\s]*\s*(\d+\.?\d*\s*[g%])"),
+            ("cholesterol", r"(?i)Cholest(?:é|e)rol\s*[:\s]*\s*(\d+\s*mg)"),
+            ("sodium", r"(?i)(?:Sodium|Sel)\s*[:\s]*\s*(\d+\.?\d*\s*[mg%]+)"),
+            ("total_carbohydrate", r"(?i)Glucides\s*[:\s]*\s*(\d+\.?\d*\s*[g%])"),
+            ("dietary_fiber", r"(?i)Fibres\s*[:\s]*\s*(\d+\.?\d*\s*[g%])"),
+            ("sugars", r"(?i)(?:dont\s+)?Sucres\s*[:\s]*\s*(\d+\.?\d*\s*[g%])"),
+            ("protein", r"(?i)Prot(?:é|e)ines\s*[:\s]*\s*(\d+\.?\d*\s*[g%])"),
+        ],
+        Lang::German => vec![
+            ("total_fat", r"(?i)Fett\s*[:\s]*\s*(\d+\.?\d*\s*[g%])"),
+            ("saturated_fat", r"(?i)(?:davon\s+)?ges(?:ä|ae)ttigte\s+Fetts(?:ä|ae)uren\s*[:\s]*\s*(\d+\.?\d*\s*[g%])"),
+            ("cholesterol", r"(?i)Cholesterin\s*[:\s]*\s*(\d+\s*mg)"),
+            ("sodium", r"(?i)Natrium\s*[:\s]*\s*(\d+\.?\d*\s*[mg%]+)"),
+            ("total_carbohydrate", r"(?i)Kohlenhydrate\s*[:\s]*\s*(\d+\.?\d*\s*[g%])"),
+            ("dietary_fiber", r"(?i)Ballaststoffe\s*[:\s]*\s*(\d+\.?\d*\s*[g%])"),
+            ("sugars", r"(?i)(?:davon\s+)?Zucker\s*[:\s]*\s*(\d+\.?\d*\s*[g%])"),
+            ("protein", r"(?i)Eiwei(?:ß|ss)\s*[:\s]*\s*(\d+\.?\d*\s*[g%])"),
+        ],
+        Lang::Spanish => vec![
+            ("total_fat", r"(?i)Grasas\s*[:\s]*\s*(\d+\.?\d*\s*[g%])"),
+            ("saturated_fat", r"(?i)Grasas\s+satur(?:a|á)das\s*[:\s]*\s*(\d+\.?\d*\s*[g%])"),
+            ("cholesterol", r"(?i)Colesterol\s*[:\s]*\s*(\d+\s*mg)"),
+            ("sodium", r"(?i)Sodio\s*[:\s]*\s*(\d+\.?\d*\s*[mg%]+)"),
+            ("total_carbohydrate", r"(?i)(?:Hidratos\s+de\s+carbono|Carbohidratos)\s*[:\s]*\s*(\d+\.?\d*\s*[g%])"),
+            ("dietary_fiber", r"(?i)Fibra\s*[:\s]*\s*(\d+\.?\d*\s*[g%])"),
+            ("sugars", r"(?i)Az(?:ú|u)cares\s*[:\s]*\s*(\d+\.?\d*\s*[g%])"),
+            ("protein", r"(?i)Prote(?:í|i)nas\s*[:\s]*\s*(\d+\.?\d*\s*[g%])"),
+        ],
+    }
+}
+
+fn ingredients_marker(lang: Lang) -> &'static str {
+    match lang {
+        Lang::English => r"(?i)(?:Ingredients|INGREDIENTS|INGREDIENT|ingredient)[:\s](.*)",
+        Lang::French => r"(?i)(?:Ingr(?:é|e)dients?)[:\s](.*)",
+        Lang::German => r"(?i)(?:Zutaten)[:\s](.*)",
+        Lang::Spanish => r"(?i)(?:Ingredientes?)[:\s](.*)",
+    }
+}
+
+fn end_markers(lang: Lang) -> Vec<&'static str> {
+    match lang {
+        Lang::English => vec![
+            "\n\n", "Nutrition Facts", "Nutritional", "Allergen", "Contains",
+            "Storage", "Best before", "Dist.", "KEEP REFRIGERATED", "how2recycle.info",
+            "PLASTIC", "BOTTLE", "CA CRV", "CTRV", "HI 5¢", "ME 5¢", "% Daily Value",
+            "Serving size", "Amount per serving", "Calories", "Total Fat", "Cholesterol",
+        ],
+        Lang::French => vec![
+            "\n\n", "Valeurs nutritionnelles", "Allergènes", "Contient", "Conservation",
+            "À consommer", "Portion", "Calories",
+        ],
+        Lang::German => vec![
+            "\n\n", "Nährwerte", "Allergene", "Enthält", "Lagerung",
+            "Mindestens haltbar", "Portion", "Kalorien",
+        ],
+        Lang::Spanish => vec![
+            "\n\n", "Información nutricional", "Alérgenos", "Contiene", "Conservación",
+            "Consumir antes de", "Porción", "Calorías",
+        ],
+    }
+}
+
+fn parse_nutrition_info(text: &str, context: &Context) -> NutritionData {
     let mut nutrition_data = NutritionData {
         serving_size: None,
         calories: None,
@@ -98,13 +596,7 @@ fn parse_nutrition_info(text: &str) -> NutritionData {
     };
     
     // Serving size patterns
-    let serving_patterns = vec![
-        r"(?i)Serving\s+Size[:\s]*([^\.]*?)(Serving|Amount|Calories|Per)",
-        r"(?i)Serving\s+Size[:\s]*([0-9]+\s*[a-zA-Z]*)",
-        r"(?i)Serving[:\s]*([0-9]+\s*[a-zA-Z]*)",
-    ];
-    
-    for pattern in serving_patterns {
+    for pattern in serving_patterns(context.lang) {
         if let Some(caps) = Regex::new(pattern).unwrap().captures(text) {
             if let Some(serving_size) = caps.get(1) {
                 nutrition_data.serving_size = Some(serving_size.as_str().trim().to_string());
@@ -114,13 +606,7 @@ fn parse_nutrition_info(text: &str) -> NutritionData {
     }
     
     // Calories patterns
-    let calories_patterns = vec![
-        r"(?i)Calories\s+(\d+)",
-        r"(?i)Energy\s+(\d+)\s*kcal",
-        r"(?i)Cal[:\s]*(\d+)",
-    ];
-    
-    for pattern in calories_patterns {
+    for pattern in calories_patterns(context.lang) {
         if let Some(caps) = Regex::new(pattern).unwrap().captures(text) {
             if let Some(calories) = caps.get(1) {
                 nutrition_data.calories = Some(calories.as_str().trim().to_string());
@@ -130,18 +616,7 @@ fn parse_nutrition_info(text: &str) -> NutritionData {
     }
     
     // Nutrient patterns
-    let nutrient_patterns = vec![
-        ("total_fat", r"(?i)Total\s+Fat\s*[:\s]*\s*(\d+\.?\d*\s*[g%])"),
-        ("saturated_fat", r"(?i)Saturated\s+Fat\s*[:\s]*\s*(\d+\.?\d*\s*[g%])"),
-        ("cholesterol", r"(?i)Cholesterol\s*[:\s]*\s*(\d+\s*mg)"),
-        ("sodium", r"(?i)Sodium\s*[:\s]*\s*(\d+\s*mg)"),
-        ("total_carbohydrate", r"(?i)(Total\s+)?Carbohydrate\s*[:\s]*\s*(\d+\.?\d*\s*[g%])"),
-        ("dietary_fiber", r"(?i)(Dietary\s+)?Fiber\s*[:\s]*\s*(\d+\.?\d*\s*[g%])"),
-        ("sugars", r"(?i)Sugars\s*[:\s]*\s*(\d+\.?\d*\s*[g%])"),
-        ("protein", r"(?i)Protein\s*[:\s]*\s*(\d+\.?\d*\s*[g%])"),
-    ];
-    
-    for (field, pattern) in nutrient_patterns {
+    for (field, pattern) in nutrient_patterns(context.lang) {
         if let Some(caps) = Regex::new(pattern).unwrap().captures(text) {
             let value = if caps.len() > 2 {
                 caps.get(2).unwrap().as_str()
@@ -164,20 +639,13 @@ fn parse_nutrition_info(text: &str) -> NutritionData {
     }
     
     // Ingredients extraction
-    if let Some(ingredients_section) = Regex::new(r"(?i)(?:Ingredients|INGREDIENTS|INGREDIENT|ingredient)[:\s](.*)")
+    if let Some(ingredients_section) = Regex::new(ingredients_marker(context.lang))
         .unwrap()
         .captures(text)
     {
         let mut potential_ingredients = ingredients_section.get(1).unwrap().as_str().trim();
-        
-        let end_markers = vec![
-            "\n\n", "Nutrition Facts", "Nutritional", "Allergen", "Contains", 
-            "Storage", "Best before", "Dist.", "KEEP REFRIGERATED", "how2recycle.info", 
-            "PLASTIC", "BOTTLE", "CA CRV", "CTRV", "HI 5¢", "ME 5¢", "% Daily Value", 
-            "Serving size", "Amount per serving", "Calories", "Total Fat", "Cholesterol"
-        ];
-        
-        for marker in end_markers {
+
+        for marker in end_markers(context.lang) {
             if let Some(pos) = potential_ingredients.to_lowercase().find(&marker.to_lowercase()) {
                 potential_ingredients = &potential_ingredients[..pos];
             }
@@ -191,30 +659,248 @@ fn parse_nutrition_info(text: &str) -> NutritionData {
     nutrition_data
 }
 
-fn check_for_allergens(ingredients_text: Option<&String>) -> Vec<String> {
+const ALLERGEN_MAP_PATH: &str = "allergen_map.json";
+
+/// Ingredient -> allergen group map learned from labels that carry an explicit
+/// "Contains:" declaration, so obscure ingredient names can be flagged even
+/// when the allergen word itself never appears in the ingredients text.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+struct AllergenMap {
+    ingredient_to_allergen: HashMap<String, String>,
+}
+
+fn tokenize_ingredients(ingredients_text: &str) -> HashSet<String> {
+    ingredients_text
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|token| token.len() > 2)
+        .map(|token| token.to_string())
+        .collect()
+}
+
+fn extract_declared_allergens(text: &str) -> HashSet<String> {
+    let mut declared = HashSet::new();
+
+    if let Some(caps) = Regex::new(r"(?i)Contains[:\s]+([^\.\n]*)").unwrap().captures(text) {
+        let list = caps.get(1).unwrap().as_str();
+        for allergen in list.split(',') {
+            let allergen = allergen.trim().trim_end_matches('.').to_lowercase();
+            if !allergen.is_empty() {
+                declared.insert(allergen);
+            }
+        }
+    }
+
+    declared
+}
+
+/// Builds an ingredient -> allergen map from a corpus of `AnalysisResult`s
+/// whose labels include a "Contains:" declaration, using constraint solving:
+/// for each allergen, intersect the ingredient sets of every product that
+/// declares it, then repeatedly lock any allergen left with exactly one
+/// candidate ingredient and remove that ingredient from the other allergens'
+/// candidate sets until no more singletons resolve.
+fn learn_allergen_map(results: &[AnalysisResult]) -> AllergenMap {
+    let mut candidates: HashMap<String, HashSet<String>> = HashMap::new();
+
+    for result in results {
+        let ingredients = match &result.nutrition_data.ingredients {
+            Some(text) => text,
+            None => continue,
+        };
+        let declared = extract_declared_allergens(&result.extracted_text);
+        if declared.is_empty() {
+            continue;
+        }
+
+        let tokens = tokenize_ingredients(ingredients);
+        for allergen in declared {
+            candidates
+                .entry(allergen)
+                .and_modify(|existing| *existing = existing.intersection(&tokens).cloned().collect())
+                .or_insert_with(|| tokens.clone());
+        }
+    }
+
+    let mut locked: HashMap<String, String> = HashMap::new();
+    loop {
+        let singleton = candidates
+            .iter()
+            .find(|(allergen, ingredients)| {
+                ingredients.len() == 1 && !locked.values().any(|locked_allergen| locked_allergen == *allergen)
+            })
+            .map(|(allergen, ingredients)| (allergen.clone(), ingredients.iter().next().unwrap().clone()));
+
+        let (allergen, ingredient) = match singleton {
+            Some(pair) => pair,
+            None => break,
+        };
+
+        locked.insert(ingredient.clone(), allergen.clone());
+        for (other_allergen, other_candidates) in candidates.iter_mut() {
+            if other_allergen != &allergen {
+                other_candidates.remove(&ingredient);
+            }
+        }
+    }
+
+    AllergenMap { ingredient_to_allergen: locked }
+}
+
+fn load_allergen_map(path: &Path) -> AllergenMap {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+fn save_allergen_map(map: &AllergenMap, path: &Path) -> Result<()> {
+    let contents = serde_json::to_string_pretty(map)?;
+    fs::write(path, contents)?;
+    Ok(())
+}
+
+/// In-memory cache of the learned allergen map, backed by a configurable
+/// on-disk path (mirrors `OcrCache::new`'s `dir` parameter) so
+/// `check_for_allergens` doesn't re-read and re-parse the file on every
+/// single call.
+pub struct AllergenMapStore {
+    path: PathBuf,
+    cached: RefCell<Option<AllergenMap>>,
+}
+
+impl AllergenMapStore {
+    pub fn new(path: impl Into<PathBuf>) -> AllergenMapStore {
+        AllergenMapStore {
+            path: path.into(),
+            cached: RefCell::new(None),
+        }
+    }
+
+    /// Returns the cached map, loading it from disk the first time it's
+    /// requested.
+    fn get(&self) -> AllergenMap {
+        if self.cached.borrow().is_none() {
+            *self.cached.borrow_mut() = Some(load_allergen_map(&self.path));
+        }
+        self.cached.borrow().clone().unwrap()
+    }
+
+    /// Drops the in-memory cache so the next `get()` re-reads the file.
+    pub fn invalidate(&self) {
+        *self.cached.borrow_mut() = None;
+    }
+
+    /// Learns an ingredient->allergen map from a corpus of prior scans,
+    /// persists it to this store's path, and invalidates the in-memory
+    /// cache so the next `get()` picks it up.
+    pub fn update(&self, corpus: &[AnalysisResult]) -> Result<()> {
+        let map = learn_allergen_map(corpus);
+        save_allergen_map(&map, &self.path)?;
+        self.invalidate();
+        Ok(())
+    }
+}
+
+impl Default for AllergenMapStore {
+    fn default() -> Self {
+        AllergenMapStore::new(ALLERGEN_MAP_PATH)
+    }
+}
+
+/// Allergen group -> term vocabulary for a given language. Group keys
+/// (e.g. "milk", "tree nuts") stay in English across all languages so the
+/// reported allergen list is normalized regardless of the label's language.
+fn allergen_groups(lang: Lang) -> Vec<(&'static str, Vec<&'static str>)> {
+    match lang {
+        Lang::English => vec![
+            ("milk", vec!["milk", "dairy", "lactose", "whey", "casein"]),
+            ("eggs", vec!["egg", "eggs"]),
+            ("peanuts", vec!["peanut", "peanuts"]),
+            ("tree nuts", vec!["tree nut", "tree nuts", "almond", "almonds", "walnut", "walnuts",
+                              "cashew", "cashews", "pistachio", "pistachios",
+                              "hazelnut", "hazelnuts", "pecan", "pecans"]),
+            ("soy", vec!["soy", "soya", "tofu", "edamame"]),
+            ("wheat/gluten", vec!["wheat", "gluten", "barley", "rye", "spelt", "triticale"]),
+            ("fish", vec!["fish"]),
+            ("shellfish", vec!["shellfish", "crustacean", "crustaceans", "shrimp", "crab", "lobster"]),
+            ("sulfites", vec!["sulfite", "sulfites"]),
+            ("sesame", vec!["sesame"]),
+            ("mustard", vec!["mustard"]),
+        ],
+        Lang::French => vec![
+            ("milk", vec!["lait", "produits laitiers", "lactose", "lactosérum", "caséine"]),
+            ("eggs", vec!["œuf", "œufs", "oeuf", "oeufs"]),
+            ("peanuts", vec!["arachide", "arachides", "cacahuète", "cacahuètes"]),
+            ("tree nuts", vec!["fruits à coque", "amande", "amandes", "noix", "noisette", "noisettes",
+                              "noix de cajou", "pistache", "pistaches", "noix de pécan"]),
+            ("soy", vec!["soja", "soya", "tofu"]),
+            ("wheat/gluten", vec!["blé", "gluten", "orge", "seigle", "épeautre"]),
+            ("fish", vec!["poisson"]),
+            ("shellfish", vec!["crustacé", "crustacés", "crevette", "crabe", "homard"]),
+            ("sulfites", vec!["sulfite", "sulfites"]),
+            ("sesame", vec!["sésame"]),
+            ("mustard", vec!["moutarde"]),
+        ],
+        Lang::German => vec![
+            ("milk", vec!["milch", "milchprodukte", "laktose", "molke", "kasein"]),
+            ("eggs", vec!["ei", "eier"]),
+            ("peanuts", vec!["erdnuss", "erdnüsse"]),
+            ("tree nuts", vec!["schalenfrüchte", "mandel", "mandeln", "walnuss", "walnüsse",
+                              "haselnuss", "haselnüsse", "cashew", "pistazie", "pistazien", "pekannuss"]),
+            ("soy", vec!["soja", "sojabohnen", "tofu"]),
+            ("wheat/gluten", vec!["weizen", "gluten", "gerste", "roggen", "dinkel"]),
+            ("fish", vec!["fisch"]),
+            ("shellfish", vec!["krebstiere", "garnele", "garnelen", "krabbe", "hummer"]),
+            ("sulfites", vec!["sulfit", "sulfite"]),
+            ("sesame", vec!["sesam"]),
+            ("mustard", vec!["senf"]),
+        ],
+        Lang::Spanish => vec![
+            ("milk", vec!["leche", "lácteos", "lactosa", "suero", "caseína"]),
+            ("eggs", vec!["huevo", "huevos"]),
+            ("peanuts", vec!["maní", "manís", "cacahuete", "cacahuetes"]),
+            ("tree nuts", vec!["frutos secos", "almendra", "almendras", "nuez", "nueces",
+                              "avellana", "avellanas", "anacardo", "pistacho", "pistachos", "pecana"]),
+            ("soy", vec!["soja", "soya", "tofu"]),
+            ("wheat/gluten", vec!["trigo", "gluten", "cebada", "centeno", "espelta"]),
+            ("fish", vec!["pescado"]),
+            ("shellfish", vec!["mariscos", "crustáceos", "camarón", "cangrejo", "langosta"]),
+            ("sulfites", vec!["sulfito", "sulfitos"]),
+            ("sesame", vec!["sésamo", "ajonjolí"]),
+            ("mustard", vec!["mostaza"]),
+        ],
+    }
+}
+
+fn may_contain_pattern(lang: Lang) -> &'static str {
+    match lang {
+        Lang::English => r"(?i)may\s+contain\s+([^\.]*)",
+        Lang::French => r"(?i)peut\s+contenir\s+([^\.]*)",
+        Lang::German => r"(?i)kann\s+(?:Spuren\s+von\s+)?([^\.]*)\s+enthalten",
+        Lang::Spanish => r"(?i)puede\s+contener\s+([^\.]*)",
+    }
+}
+
+fn check_for_allergens(
+    ingredients_text: Option<&String>,
+    context: &Context,
+    allergen_map: Option<&AllergenMapStore>,
+) -> Vec<String> {
     let mut found_allergens = Vec::new();
-    
+
     let ingredients_text = match ingredients_text {
         Some(text) => text.to_lowercase(),
         None => return found_allergens,
     };
-    
-    let common_allergens = vec![
-        "milk", "dairy", "lactose", "whey", "casein",
-        "egg", "eggs",
-        "peanut", "peanuts",
-        "tree nut", "tree nuts", "almond", "almonds", "walnut", "walnuts", 
-        "cashew", "cashews", "pistachio", "pistachios", 
-        "hazelnut", "hazelnuts", "pecan", "pecans",
-        "soy", "soya", "tofu", "edamame",
-        "wheat", "gluten", "barley", "rye", "spelt", "triticale",
-        "fish", "shellfish", "crustacean", "crustaceans", 
-        "shrimp", "crab", "lobster",
-        "sulfite", "sulfites",
-        "sesame", "mustard"
-    ];
-    
-    for allergen in common_allergens {
+
+    let allergen_groups = allergen_groups(context.lang);
+    let common_allergens: Vec<&str> = allergen_groups
+        .iter()
+        .flat_map(|(_, terms)| terms.iter().copied())
+        .collect();
+
+    for allergen in &common_allergens {
         if Regex::new(&format!(r"\b{}\b", regex::escape(allergen)))
             .unwrap()
             .is_match(&ingredients_text)
@@ -222,14 +908,14 @@ fn check_for_allergens(ingredients_text: Option<&String>) -> Vec<String> {
             found_allergens.push(allergen.to_string());
         }
     }
-    
+
     // Check for "may contain" statements
-    if let Some(caps) = Regex::new(r"(?i)may\s+contain\s+([^\.]*)")
+    if let Some(caps) = Regex::new(may_contain_pattern(context.lang))
         .unwrap()
         .captures(&ingredients_text)
     {
         let may_contain_text = caps.get(1).unwrap().as_str();
-        for allergen in common_allergens {
+        for allergen in &common_allergens {
             if Regex::new(&format!(r"\b{}\b", regex::escape(allergen)))
                 .unwrap()
                 .is_match(may_contain_text)
@@ -238,34 +924,166 @@ fn check_for_allergens(ingredients_text: Option<&String>) -> Vec<String> {
             }
         }
     }
-    
+
     // Deduplicate allergens
-    let allergen_groups = vec![
-        ("milk", vec!["milk", "dairy", "lactose", "whey", "casein"]),
-        ("eggs", vec!["egg", "eggs"]),
-        ("peanuts", vec!["peanut", "peanuts"]),
-        ("tree nuts", vec!["tree nut", "tree nuts", "almond", "almonds", "walnut", "walnuts", 
-                          "cashew", "cashews", "pistachio", "pistachios", 
-                          "hazelnut", "hazelnuts", "pecan", "pecans"]),
-        ("soy", vec!["soy", "soya", "tofu", "edamame"]),
-        ("wheat/gluten", vec!["wheat", "gluten", "barley", "rye", "spelt", "triticale"]),
-        ("fish", vec!["fish"]),
-        ("shellfish", vec!["shellfish", "crustacean", "crustaceans", "shrimp", "crab", "lobster"]),
-        ("sulfites", vec!["sulfite", "sulfites"]),
-        ("sesame", vec!["sesame"]),
-        ("mustard", vec!["mustard"]),
-    ];
-    
     let mut deduplicated = Vec::new();
     for (group, items) in allergen_groups {
         if found_allergens.iter().any(|a| items.contains(&a.as_str())) {
             deduplicated.push(group.to_string());
         }
     }
-    
+
+    // Consult the learned ingredient->allergen map so obscure ingredient
+    // names (e.g. "sodium caseinate") are flagged even when the allergen
+    // word never appears in the ingredients text. Only touches disk (once,
+    // cached) when the caller actually provides a store.
+    if let Some(store) = allergen_map {
+        let learned_map = store.get();
+        if !learned_map.ingredient_to_allergen.is_empty() {
+            let tokens = tokenize_ingredients(&ingredients_text);
+            for token in &tokens {
+                if let Some(group) = learned_map.ingredient_to_allergen.get(token) {
+                    if !deduplicated.contains(group) {
+                        deduplicated.push(group.clone());
+                    }
+                }
+            }
+        }
+    }
+
     deduplicated
 }
 
+/// Default minimum normalized similarity for `fuzzy_match_allergens` to
+/// report a token, used by `generate_response` when a caller hasn't tuned
+/// one. Catches single-character OCR noise in terms of seven or more
+/// characters ("haze1nut") without flooding the response with unrelated
+/// short words; terms of four characters or fewer (e.g. "milk", so "mi1k"
+/// is not flagged) only match exactly, see `min_similarity_for_term`.
+const DEFAULT_FUZZY_THRESHOLD: f64 = 0.75;
+
+/// A token from the ingredients text that didn't match the exact keyword
+/// pass but scored above the similarity threshold against a known allergen
+/// term, e.g. OCR noise ("haze1nut") or an unlisted synonym.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FuzzyAllergenMatch {
+    pub token: String,
+    pub allergen_group: String,
+    pub confidence: f64,
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut dp = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+
+    for (i, row) in dp.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for j in 0..=b.len() {
+        dp[0][j] = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            dp[i][j] = (dp[i - 1][j] + 1)
+                .min(dp[i][j - 1] + 1)
+                .min(dp[i - 1][j - 1] + cost);
+        }
+    }
+
+    dp[a.len()][b.len()]
+}
+
+fn normalized_similarity(a: &str, b: &str) -> f64 {
+    let max_len = a.chars().count().max(b.chars().count());
+    if max_len == 0 {
+        return 1.0;
+    }
+    1.0 - (levenshtein(a, b) as f64 / max_len as f64)
+}
+
+/// Minimum similarity required to fuzzy-match against a term of `term_len`
+/// characters. Short terms ("fish", "milk", "soy") are common English words
+/// that sit one edit away from lots of unrelated tokens ("dish"/"wish" vs
+/// "fish", "silk"/"mild" vs "milk" all score a flat 0.75) — a single typo's
+/// worth of tolerance on a 4-letter word covers too much of the dictionary.
+/// Longer terms have more characters "to spend" on a genuine typo or OCR
+/// misread before collapsing into another real word: terms of five or six
+/// characters need at least 0.85, and terms of seven or more keep the
+/// caller's requested `base_threshold` unscaled.
+fn min_similarity_for_term(term_len: usize, base_threshold: f64) -> f64 {
+    match term_len {
+        0..=4 => 1.0,
+        5..=6 => base_threshold.max(0.85),
+        _ => base_threshold,
+    }
+}
+
+/// Tokenizes ingredients text and, for tokens the exact keyword pass didn't
+/// already catch, scores each against the known allergen vocabulary via
+/// normalized edit distance. Flags any token whose best match clears
+/// `min_similarity_for_term`'s (length-scaled) bar, reporting the matched
+/// allergen group and a confidence score — catching OCR noise ("haze1nut",
+/// "pistach1os") and unlisted synonyms that exact `\bword\b` matching misses,
+/// without short real words tripping the same check. Noise in terms of
+/// four characters or fewer ("whev") is not flagged, and a single misread
+/// in a five- or six-character term ("waln8t") falls below its bar.
+pub fn fuzzy_match_allergens(ingredients_text: &str, context: &Context, threshold: f64) -> Vec<FuzzyAllergenMatch> {
+    let groups = allergen_groups(context.lang);
+    let exact_terms: HashSet<&str> = groups.iter().flat_map(|(_, terms)| terms.iter().copied()).collect();
+
+    let mut matches = Vec::new();
+    for token in tokenize_ingredients(ingredients_text) {
+        if exact_terms.contains(token.as_str()) {
+            continue;
+        }
+
+        let best = groups
+            .iter()
+            .flat_map(|(group, terms)| terms.iter().map(move |term| (*group, normalized_similarity(&token, term), term.chars().count())))
+            .filter(|(_, confidence, term_len)| *confidence >= min_similarity_for_term(*term_len, threshold))
+            .max_by(|(_, a, _), (_, b, _)| a.partial_cmp(b).unwrap());
+
+        if let Some((group, confidence, _)) = best {
+            matches.push(FuzzyAllergenMatch {
+                token: token.clone(),
+                allergen_group: group.to_string(),
+                confidence,
+            });
+        }
+    }
+
+    matches
+}
+
+/// Hybrid allergen check: runs the fast exact keyword pass (zero overhead,
+/// handles the common case) and layers the fuzzy matcher on top so tokens
+/// clearing `fuzzy_threshold` (raised for short terms, see
+/// `min_similarity_for_term`) are also reported, alongside their confidence.
+pub fn check_for_allergens_hybrid(
+    ingredients_text: Option<&String>,
+    context: &Context,
+    allergen_map: Option<&AllergenMapStore>,
+    fuzzy_threshold: f64,
+) -> (Vec<String>, Vec<FuzzyAllergenMatch>) {
+    let mut groups = check_for_allergens(ingredients_text, context, allergen_map);
+
+    let fuzzy_matches = match ingredients_text {
+        Some(text) => fuzzy_match_allergens(text, context, fuzzy_threshold),
+        None => Vec::new(),
+    };
+
+    for fuzzy_match in &fuzzy_matches {
+        if !groups.contains(&fuzzy_match.allergen_group) {
+            groups.push(fuzzy_match.allergen_group.clone());
+        }
+    }
+
+    (groups, fuzzy_matches)
+}
+
 fn check_nutritional_concerns(nutrition_data: &NutritionData) -> Vec<String> {
     let mut concerns = Vec::new();
     
@@ -302,26 +1120,44 @@ fn check_nutritional_concerns(nutrition_data: &NutritionData) -> Vec<String> {
     concerns
 }
 
-fn generate_response(nutrition_data: &NutritionData, extracted_text: &str) -> String {
-    let is_valid_data = nutrition_data.serving_size.is_some() 
+fn generate_response(
+    nutrition_data: &NutritionData,
+    extracted_text: &str,
+    context: &Context,
+    allergen_map: Option<&AllergenMapStore>,
+) -> String {
+    let is_valid_data = nutrition_data.serving_size.is_some()
         || nutrition_data.calories.is_some()
         || nutrition_data.total_fat.is_some()
         || nutrition_data.sodium.is_some();
-    
+
     if !is_valid_data {
         return "Sorry, I couldn't detect clear nutrition information from the image. Please try a clearer photo of the nutrition label.".to_string();
     }
-    
+
     let mut response = String::new();
-    
-    // Allergen information
-    let allergens = check_for_allergens(nutrition_data.ingredients.as_ref());
+
+    // Allergen information, including fuzzy matches for OCR noise and
+    // unlisted synonyms (e.g. "haze1nut") that the exact keyword pass misses
+    let (allergens, fuzzy_matches) = check_for_allergens_hybrid(
+        nutrition_data.ingredients.as_ref(),
+        context,
+        allergen_map,
+        DEFAULT_FUZZY_THRESHOLD,
+    );
     if !allergens.is_empty() {
         response.push_str(&format!("Alert: This product contains potential allergens ({}). ", allergens.join(", ")));
     } else {
         response.push_str("No common allergens detected in the ingredients. ");
     }
-    
+    if !fuzzy_matches.is_empty() {
+        let fuzzy_summary: Vec<String> = fuzzy_matches
+            .iter()
+            .map(|m| format!("{} ({:.0}% match to {})", m.token, m.confidence * 100.0, m.allergen_group))
+            .collect();
+        response.push_str(&format!("Possible allergens detected via fuzzy matching: {}. ", fuzzy_summary.join(", ")));
+    }
+
     // Nutritional concerns
     let concerns = check_nutritional_concerns(nutrition_data);
     if !concerns.is_empty() {
@@ -343,30 +1179,54 @@ fn generate_response(nutrition_data: &NutritionData, extracted_text: &str) -> St
     response
 }
 
-pub async fn analyze_nutrition_label(image_path: &str) -> Result<AnalysisResult> {
-    // Detect text
-    let extracted_text = detect_text(image_path).await?;
+pub async fn analyze_nutrition_label(
+    image_path: &str,
+    format: OutputFormat,
+    context: Option<Context>,
+    cache: Option<&OcrCache>,
+    allergen_map: Option<&AllergenMapStore>,
+) -> Result<AnalysisOutput> {
+    // Detect text, consulting the cache when the caller provides one
+    let extracted_text = match cache {
+        Some(cache) => detect_text_cached(image_path, cache).await?,
+        None => detect_text(image_path).await?,
+    };
     let normalized_text = normalize_units(&extracted_text);
-    
+
+    // Auto-detect the label's language when the caller doesn't specify one
+    let context = context.unwrap_or_else(|| Context::new(detect_language(&normalized_text)));
+
     // Parse nutrition information
-    let nutrition_data = parse_nutrition_info(&normalized_text);
-    
+    let nutrition_data = parse_nutrition_info(&normalized_text, &context);
+
     // Generate response
-    let response = generate_response(&nutrition_data, &normalized_text);
-    
-    Ok(AnalysisResult {
-        extracted_text: normalized_text,
-        nutrition_data,
-        response,
-    })
+    let response = generate_response(&nutrition_data, &normalized_text, &context, allergen_map);
+
+    match format {
+        OutputFormat::Native => Ok(AnalysisOutput::Native(AnalysisResult {
+            extracted_text: normalized_text,
+            nutrition_data,
+            response,
+        })),
+        OutputFormat::SchemaOrgNutrition => {
+            Ok(AnalysisOutput::SchemaOrgNutrition(SchemaOrgNutrition::from(&nutrition_data)))
+        }
+        OutputFormat::SchemaOrgRecipe => {
+            Ok(AnalysisOutput::SchemaOrgRecipe(SchemaOrgRecipe::from(&nutrition_data)))
+        }
+    }
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let image_path = "path/to/your/nutrition_label.jpg";
-    
-    let result = analyze_nutrition_label(image_path).await?;
-    
+
+    let allergen_map = AllergenMapStore::default();
+    let result = match analyze_nutrition_label(image_path, OutputFormat::Native, None, None, Some(&allergen_map)).await? {
+        AnalysisOutput::Native(result) => result,
+        _ => unreachable!("OutputFormat::Native always returns AnalysisOutput::Native"),
+    };
+
     println!("--- Extracted Text ---");
     println!("{}", if result.extracted_text.len() > 500 {
         &result.extracted_text[..500]
@@ -379,6 +1239,305 @@ async fn main() -> Result<()> {
     
     println!("\n--- Analysis Result ---");
     println!("{}", result.response);
-    
+
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn empty_nutrition_data() -> NutritionData {
+        NutritionData {
+            serving_size: None,
+            calories: None,
+            total_fat: None,
+            saturated_fat: None,
+            cholesterol: None,
+            sodium: None,
+            total_carbohydrate: None,
+            dietary_fiber: None,
+            sugars: None,
+            protein: None,
+            ingredients: None,
+        }
+    }
+
+    #[test]
+    fn extract_amount_unit_parses_leading_quantity() {
+        let (name, amount, unit) = extract_amount_unit("2g Salt");
+        assert_eq!(name, "Salt");
+        assert_eq!(amount, Some(2.0));
+        assert_eq!(unit, Unit::Gram);
+    }
+
+    #[test]
+    fn extract_amount_unit_parses_trailing_quantity() {
+        let (name, amount, unit) = extract_amount_unit("Salt 2g");
+        assert_eq!(name, "Salt");
+        assert_eq!(amount, Some(2.0));
+        assert_eq!(unit, Unit::Gram);
+    }
+
+    #[test]
+    fn extract_amount_unit_handles_unmatched_unit() {
+        let (name, amount, unit) = extract_amount_unit("5 Eggs");
+        assert_eq!(name, "Eggs");
+        assert_eq!(amount, Some(5.0));
+        assert_eq!(unit, Unit::None);
+    }
+
+    #[test]
+    fn extract_amount_unit_falls_back_when_no_quantity() {
+        let (name, amount, unit) = extract_amount_unit("Natural Flavors");
+        assert_eq!(name, "Natural Flavors");
+        assert_eq!(amount, None);
+        assert_eq!(unit, Unit::None);
+    }
+
+    #[test]
+    fn parse_ingredients_respects_nested_parentheses() {
+        let parsed = parse_ingredients("Enriched Flour (Wheat Flour, Niacin, Reduced Iron), Water, Salt");
+        assert_eq!(parsed.len(), 3);
+        assert_eq!(parsed[0].name, "Enriched Flour (Wheat Flour, Niacin, Reduced Iron)");
+        assert_eq!(parsed[1].name, "Water");
+        assert_eq!(parsed[2].name, "Salt");
+    }
+
+    #[test]
+    fn merge_ingredients_sums_matching_name_and_unit() {
+        let a = vec![Ingredient { name: "Salt".to_string(), amount: Some(2.0), unit: Unit::Gram }];
+        let b = vec![Ingredient { name: "Salt".to_string(), amount: Some(3.0), unit: Unit::Gram }];
+
+        let merged = merge_ingredients(&[a, b]);
+
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged[0].amount, Some(5.0));
+        assert_eq!(merged[0].unit, Unit::Gram);
+    }
+
+    #[test]
+    fn merge_ingredients_keeps_different_units_separate() {
+        let a = vec![Ingredient { name: "Sugar".to_string(), amount: Some(10.0), unit: Unit::Gram }];
+        let b = vec![Ingredient { name: "Sugar".to_string(), amount: Some(1.0), unit: Unit::Percent }];
+
+        let merged = merge_ingredients(&[a, b]);
+
+        assert_eq!(merged.len(), 2);
+    }
+
+    #[test]
+    fn schema_org_amount_reformats_recognized_units() {
+        assert_eq!(schema_org_amount(&Some("12g".to_string())), Some("12 g".to_string()));
+        assert_eq!(schema_org_amount(&Some("500 mg".to_string())), Some("500 mg".to_string()));
+        assert_eq!(schema_org_amount(&Some("3.5%".to_string())), Some("3.5 %".to_string()));
+    }
+
+    #[test]
+    fn schema_org_amount_leaves_unparsable_values_untouched() {
+        assert_eq!(schema_org_amount(&Some("a lot".to_string())), Some("a lot".to_string()));
+        assert_eq!(schema_org_amount(&None), None);
+    }
+
+    #[test]
+    fn schema_org_nutrition_from_maps_fields_and_reformats_amounts() {
+        let mut data = empty_nutrition_data();
+        data.serving_size = Some("1 cup".to_string());
+        data.calories = Some("120".to_string());
+        data.total_fat = Some("5g".to_string());
+        data.sodium = Some("200mg".to_string());
+
+        let nutrition = SchemaOrgNutrition::from(&data);
+
+        assert_eq!(nutrition.schema_type, "NutritionInformation");
+        assert_eq!(nutrition.serving_size, Some("1 cup".to_string()));
+        assert_eq!(nutrition.calories, Some("120".to_string()));
+        assert_eq!(nutrition.fat_content, Some("5 g".to_string()));
+        assert_eq!(nutrition.sodium_content, Some("200 mg".to_string()));
+        assert_eq!(nutrition.saturated_fat_content, None);
+    }
+
+    #[test]
+    fn schema_org_nutrition_serializes_with_camel_case_keys() {
+        let mut data = empty_nutrition_data();
+        data.serving_size = Some("1 cup".to_string());
+        data.total_fat = Some("5g".to_string());
+
+        let json = serde_json::to_value(SchemaOrgNutrition::from(&data)).unwrap();
+
+        assert_eq!(json["@type"], "NutritionInformation");
+        assert_eq!(json["servingSize"], "1 cup");
+        assert_eq!(json["fatContent"], "5 g");
+        assert!(json.get("saturatedFatContent").is_none());
+    }
+
+    #[test]
+    fn schema_org_recipe_preserves_nested_sub_ingredients() {
+        let mut data = empty_nutrition_data();
+        data.ingredients = Some("Enriched Flour (Wheat Flour, Niacin, Reduced Iron), Water".to_string());
+
+        let recipe = SchemaOrgRecipe::from(&data);
+        let recipe_ingredient = recipe.recipe_ingredient.expect("ingredients were set");
+
+        assert_eq!(recipe_ingredient.len(), 2);
+        assert!(recipe_ingredient[0].contains("Wheat Flour, Niacin, Reduced Iron"));
+    }
+
+    fn analysis_result(extracted_text: &str, ingredients: &str) -> AnalysisResult {
+        let mut data = empty_nutrition_data();
+        data.ingredients = Some(ingredients.to_string());
+        AnalysisResult {
+            extracted_text: extracted_text.to_string(),
+            nutrition_data: data,
+            response: String::new(),
+        }
+    }
+
+    /// Unique path under the OS temp dir for self-cleaning fixtures, e.g.
+    /// `temp_fixture_path("allergen-map", ".json")` for a file or
+    /// `temp_fixture_path("ocr-cache", "")` for a directory.
+    fn temp_fixture_path(name: &str, suffix: &str) -> PathBuf {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+        std::env::temp_dir().join(format!("{}-{}-{}{}", name, std::process::id(), nanos, suffix))
+    }
+
+    #[test]
+    fn learn_allergen_map_locks_singleton_candidates() {
+        // "caseinate" is the only ingredient word common to every product
+        // declaring "milk" (and "lecithin" to every product declaring "soy"),
+        // so it should get locked to the milk group even though the word
+        // "milk" never appears in the ingredients list.
+        let corpus = vec![
+            analysis_result("Contains: Milk", "Sodium Caseinate, Water"),
+            analysis_result("Contains: Milk", "Calcium Caseinate, Salt"),
+            analysis_result("Contains: Soy", "Soy Lecithin, Water"),
+            analysis_result("Contains: Soy", "Lecithin, Salt"),
+        ];
+
+        let map = learn_allergen_map(&corpus);
+
+        assert_eq!(map.ingredient_to_allergen.get("caseinate").map(String::as_str), Some("milk"));
+        assert_eq!(map.ingredient_to_allergen.get("lecithin").map(String::as_str), Some("soy"));
+        // "water" appears in products of both groups but not in every
+        // product of either, so intersection drops it from both candidate
+        // sets and it must not get locked to either.
+        assert!(!map.ingredient_to_allergen.contains_key("water"));
+    }
+
+    #[test]
+    fn allergen_map_store_caches_after_first_load() {
+        let path = temp_fixture_path("allergen-map-store-test", ".json");
+        let store = AllergenMapStore::new(&path);
+
+        let corpus = vec![
+            analysis_result("Contains: Milk", "Sodium Caseinate, Water"),
+            analysis_result("Contains: Milk", "Calcium Caseinate, Salt"),
+        ];
+        store.update(&corpus).expect("update should persist the learned map");
+
+        // First load reads the file `update` just wrote into the cache.
+        store.get();
+
+        // Remove the backing file: if `get()` re-read from disk instead of
+        // using its cache, this would return an empty map.
+        fs::remove_file(&path).unwrap();
+        let map = store.get();
+        assert_eq!(map.ingredient_to_allergen.get("caseinate").map(String::as_str), Some("milk"));
+
+        store.invalidate();
+    }
+
+    #[test]
+    fn detect_language_recognizes_french_keywords() {
+        let text = "Valeurs nutritionnelles\nIngrédients: Farine, Sel, Lipides 5g";
+        assert_eq!(detect_language(text), Lang::French);
+    }
+
+    #[test]
+    fn parse_nutrition_info_parses_french_label() {
+        let text = "Portion 30g\nÉnergie 120 kcal\nLipides: 5g\nSodium: 200mg";
+        let context = Context::new(Lang::French);
+
+        let data = parse_nutrition_info(text, &context);
+
+        assert_eq!(data.serving_size.as_deref(), Some("30g"));
+        assert_eq!(data.calories.as_deref(), Some("120"));
+        assert_eq!(data.total_fat.as_deref(), Some("5g"));
+        assert_eq!(data.sodium.as_deref(), Some("200mg"));
+    }
+
+    #[test]
+    fn fuzzy_match_allergens_catches_ocr_noise() {
+        let context = Context::new(Lang::English);
+        let matches = fuzzy_match_allergens("Sugar, Haze1nut Paste, Salt", &context, DEFAULT_FUZZY_THRESHOLD);
+
+        assert!(matches.iter().any(|m| m.token == "haze1nut" && m.allergen_group == "tree nuts"));
+    }
+
+    #[test]
+    fn fuzzy_match_allergens_ignores_short_real_words() {
+        // "dish"/"wish" are one edit from "fish" and "silk"/"mild" are one
+        // edit from "milk" (both score a flat 0.75) but are ordinary English
+        // words, not allergen synonyms or OCR noise — short terms require an
+        // exact match so these don't trip a false "contains fish" alert.
+        let context = Context::new(Lang::English);
+        let matches = fuzzy_match_allergens("Dish towel, Wish, Silk thread, Mild sauce", &context, DEFAULT_FUZZY_THRESHOLD);
+
+        assert!(matches.is_empty());
+    }
+
+    #[test]
+    fn check_for_allergens_hybrid_merges_exact_and_fuzzy_groups() {
+        let context = Context::new(Lang::English);
+        let ingredients = "Wheat Flour, Haze1nut Paste".to_string();
+
+        let (groups, fuzzy_matches) = check_for_allergens_hybrid(Some(&ingredients), &context, None, DEFAULT_FUZZY_THRESHOLD);
+
+        assert!(groups.contains(&"wheat/gluten".to_string()));
+        assert!(groups.contains(&"tree nuts".to_string()));
+        assert!(fuzzy_matches.iter().any(|m| m.token == "haze1nut"));
+    }
+
+    #[test]
+    fn hash_image_bytes_is_stable_and_content_addressed() {
+        assert_eq!(hash_image_bytes(b"hello"), hash_image_bytes(b"hello"));
+        assert_ne!(hash_image_bytes(b"hello"), hash_image_bytes(b"world"));
+    }
+
+    #[test]
+    fn ocr_cache_returns_fetched_entry_within_ttl() {
+        let dir = temp_fixture_path("ocr-cache-fresh", "");
+        let cache = OcrCache::new(&dir, Duration::from_secs(3600)).unwrap();
+
+        cache.put("abc123", "Calories 100").unwrap();
+
+        assert!(matches!(cache.get("abc123"), Fetchable::Fetched(ref text) if text == "Calories 100"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn ocr_cache_treats_expired_entry_as_unfetched() {
+        let dir = temp_fixture_path("ocr-cache-expired", "");
+        let cache = OcrCache::new(&dir, Duration::from_secs(0)).unwrap();
+
+        cache.put("abc123", "Calories 100").unwrap();
+        // Zero TTL: ages are whole seconds, so the entry goes stale once a
+        // full second has passed since it was written.
+        std::thread::sleep(Duration::from_millis(1100));
+
+        assert!(matches!(cache.get("abc123"), Fetchable::None));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn ocr_cache_miss_for_unknown_hash() {
+        let dir = temp_fixture_path("ocr-cache-miss", "");
+        let cache = OcrCache::new(&dir, Duration::from_secs(3600)).unwrap();
+
+        assert!(matches!(cache.get("does-not-exist"), Fetchable::None));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}